@@ -1,40 +1,440 @@
 // The actual common logic.
+use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::io::Write;
+use std::path::Path;
 use std::process;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// One way to launch the compiler: either the frozen executable or a Python
+/// interpreter plus the script to feed it.
+#[derive(Debug, Clone)]
+enum Profile {
+    Frozen { exe: String },
+    Source {
+        exe: String,
+        script: String,
+        args: Vec<String>,
+        env: HashMap<String, String>,
+    },
+}
+
+impl Profile {
+    /// The executable this profile will spawn.
+    fn exe(&self) -> &str {
+        match self {
+            Profile::Frozen { exe } => exe,
+            Profile::Source { exe, .. } => exe,
+        }
+    }
+}
+
+/// A `[frozen]` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FrozenSection {
+    exe: String,
+}
+
+/// A `[source]` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SourceSection {
+    exe: String,
+    script: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    env: HashMap<String, String>,
+}
+
+/// A single profile pair, used both at the top level and for each per-compiler
+/// override table (e.g. `[vbsp]`, `[vrad]`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ProfileSet {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frozen: Option<FrozenSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<SourceSection>,
+}
+
+impl ProfileSet {
+    /// Pick a profile, preferring the Source pair when both are present.
+    fn pick(&self) -> Option<Profile> {
+        if let Some(src) = &self.source {
+            Some(Profile::Source {
+                exe: src.exe.clone(),
+                script: src.script.clone(),
+                args: src.args.clone(),
+                env: src.env.clone(),
+            })
+        } else {
+            self.frozen
+                .as_ref()
+                .map(|f| Profile::Frozen { exe: f.exe.clone() })
+        }
+    }
+}
+
+/// The parsed `bee2/app_loc.paths` document. The global `[frozen]`/`[source]`
+/// tables are the default, and any other table (keyed by `comp_name`) overrides
+/// them for that specific compiler.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Config {
+    #[serde(flatten)]
+    global: ProfileSet,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    replace: Vec<ReplaceRule>,
+    #[serde(flatten)]
+    overrides: HashMap<String, ProfileSet>,
+}
+
+/// An ordered output rewrite rule. `find` is a plain substring unless `regex`
+/// is set, in which case it is a regular expression and `replace` may contain
+/// `$1`-style capture references.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ReplaceRule {
+    find: String,
+    replace: String,
+    #[serde(default)]
+    regex: bool,
+}
+
+/// A compiled [`ReplaceRule`], ready to apply to a line.
 #[derive(Debug)]
-enum Config {
-    Frozen(String),
-    PySource { exe: String, script: String },
+enum Rewrite {
+    Literal { find: String, replace: String },
+    Regex { re: Regex, replace: String },
 }
 
-fn parse_config(conf: String) -> Config {
-    match conf.chars().position(|x| x == '\n') {
-        Some(sep) => {
-            Config::PySource {
-                exe: String::from(conf.get(..sep).unwrap()),
-                script: String::from(conf.get(sep + 1..).unwrap())
+impl Rewrite {
+    fn apply(&self, line: &str) -> String {
+        match self {
+            Rewrite::Literal { find, replace } => line.replace(find, replace),
+            Rewrite::Regex { re, replace } => re.replace_all(line, replace.as_str()).into_owned(),
+        }
+    }
+}
+
+/// Compile the configured rules, failing on the first invalid regex.
+fn compile_rules(rules: &[ReplaceRule]) -> Result<Vec<Rewrite>, String> {
+    rules
+        .iter()
+        .map(|rule| {
+            if rule.regex {
+                let re = Regex::new(&rule.find)
+                    .map_err(|e| format!("Invalid replace regex {:?}: {}", rule.find, e))?;
+                Ok(Rewrite::Regex {
+                    re,
+                    replace: rule.replace.clone(),
+                })
+            } else {
+                Ok(Rewrite::Literal {
+                    find: rule.find.clone(),
+                    replace: rule.replace.clone(),
+                })
+            }
+        })
+        .collect()
+}
+
+/// Apply the rewrite rules to each line of captured compiler output, preserving
+/// line boundaries so streamed logs keep their original ordering.
+fn rewrite_output(raw: &[u8], rules: &[Rewrite]) -> String {
+    let text = String::from_utf8_lossy(raw);
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let (body, ending) = match line.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (line, ""),
+        };
+        let mut rewritten = body.to_string();
+        for rule in rules {
+            rewritten = rule.apply(&rewritten);
+        }
+        out.push_str(&rewritten);
+        out.push_str(ending);
+    }
+    out
+}
+
+impl Config {
+    /// Resolve the profile to use for `comp_name`: the per-compiler override if
+    /// one is configured, otherwise the global profile.
+    fn resolve(&self, comp_name: &str) -> Result<Profile, String> {
+        if let Some(profile) = self.overrides.get(comp_name).and_then(ProfileSet::pick) {
+            return Ok(profile);
+        }
+        self.global
+            .pick()
+            .ok_or_else(|| "No [frozen] or [source] profile configured.".to_string())
+    }
+}
+
+/// Parse the contents of `app_loc.paths`.
+///
+/// The current format is a small TOML document, but the previous format was a
+/// bare interpreter path optionally followed by a newline and the script path;
+/// files written by older app versions are still accepted.
+fn parse_config(conf: &str) -> Result<Config, String> {
+    // Try TOML first; a legacy `exe` or `exe\nscript` path can itself contain a
+    // literal `=`, so sniffing on that character alone would misparse it as
+    // TOML and hard-error instead of falling back. Only surface the TOML error
+    // if the text doesn't even look like the legacy format (more than the two
+    // lines it can ever have).
+    match toml::from_str(conf) {
+        Ok(config) => Ok(config),
+        Err(err) => {
+            if conf.lines().count() <= 2 {
+                Ok(parse_legacy(conf))
+            } else {
+                Err(format!("Invalid config: {}", err))
             }
+        }
+    }
+}
+
+/// Parse the legacy newline-delimited format: `exe` on its own is a frozen
+/// compiler, `exe\nscript` is a Python interpreter plus script.
+fn parse_legacy(conf: &str) -> Config {
+    let conf = conf.trim_end_matches(['\r', '\n']);
+    let global = match conf.split_once('\n') {
+        Some((exe, script)) => ProfileSet {
+            frozen: None,
+            source: Some(SourceSection {
+                exe: exe.trim_end_matches('\r').to_string(),
+                script: script.to_string(),
+                args: Vec::new(),
+                env: HashMap::new(),
+            }),
         },
-        None => Config::Frozen(conf),
+        None => ProfileSet {
+            frozen: Some(FrozenSection {
+                exe: conf.to_string(),
+            }),
+            source: None,
+        },
+    };
+    Config {
+        global,
+        replace: Vec::new(),
+        overrides: HashMap::new(),
+    }
+}
+
+/// Check that a configured exe is actually present. Paths are checked on disk;
+/// bare command names are left for the OS to resolve against `PATH`.
+fn exe_exists(exe: &str) -> bool {
+    let path = Path::new(exe);
+    if path.is_absolute() || path.components().count() > 1 {
+        path.exists()
+    } else {
+        true
     }
 }
 
+/// Resolve the profile for `comp_name`, preferring the on-disk config and
+/// falling back to [`autodetect`] when it is missing or names a vanished exe.
+/// A successful autodetection is written back to `app_loc.paths` so later runs
+/// skip the probing. Returns the profile to spawn alongside the configured
+/// output rewrite rules, since those live on the `Config` rather than the
+/// `Profile` itself.
+fn resolve_profile(comp_name: &str) -> Result<(Profile, Vec<ReplaceRule>), String> {
+    // Keep the existing config around (even once it's known to be stale for
+    // `comp_name`) so a cache write below only touches the section that was
+    // actually wrong instead of clobbering the rest of the file.
+    let existing = match fs::read_to_string("bee2/app_loc.paths") {
+        Ok(conf) => {
+            let config = parse_config(&conf)?;
+            match config.resolve(comp_name) {
+                Ok(profile) if exe_exists(profile.exe()) => return Ok((profile, config.replace)),
+                Ok(profile) => eprintln!(
+                    "Configured compiler {:?} no longer exists; autodetecting.",
+                    profile.exe()
+                ),
+                Err(err) => eprintln!("{}; autodetecting.", err),
+            }
+            Some(config)
+        }
+        Err(err) => {
+            eprintln!("No BEE config file ({}); autodetecting.", err);
+            None
+        }
+    };
+
+    let profile = autodetect()?;
+    if let Err(err) = cache_profile(comp_name, existing.as_ref(), &profile) {
+        eprintln!("BEE compiler hook: could not cache autodetected config: {}", err);
+    }
+    Ok((profile, existing.map(|c| c.replace).unwrap_or_default()))
+}
+
+/// Probe for a usable compiler when the config cannot be trusted, in priority
+/// order: an explicit `BEE2_APP_LOC` exe, a `python`/`python3` on `PATH` beside
+/// a conventionally-placed `BEE2.py`, then the frozen `compiler.exe` next to
+/// this shim. Returns the first candidate whose exe exists, or an error listing
+/// everywhere that was tried.
+fn autodetect() -> Result<Profile, String> {
+    let mut tried = Vec::new();
+
+    if let Some(exe) = env::var_os("BEE2_APP_LOC") {
+        let exe = exe.to_string_lossy().into_owned();
+        if exe_exists(&exe) {
+            return Ok(Profile::Frozen { exe });
+        }
+        tried.push(format!("BEE2_APP_LOC={}", exe));
+    } else {
+        tried.push("BEE2_APP_LOC (unset)".to_string());
+    }
+
+    if let Some(script) = find_script() {
+        if let Some(exe) = find_on_path(&["python3", "python"]) {
+            return Ok(Profile::Source {
+                exe,
+                script,
+                args: Vec::new(),
+                env: HashMap::new(),
+            });
+        }
+        tried.push("python/python3 on PATH".to_string());
+    } else {
+        tried.push("BEE2.py beside shim or in working dir".to_string());
+    }
+
+    match env::current_exe().ok().and_then(|p| p.parent().map(|d| d.join("compiler.exe"))) {
+        Some(exe) if exe.exists() => {
+            return Ok(Profile::Frozen {
+                exe: exe.to_string_lossy().into_owned(),
+            });
+        }
+        Some(exe) => tried.push(exe.to_string_lossy().into_owned()),
+        None => tried.push("compiler.exe beside shim".to_string()),
+    }
+
+    Err(format!(
+        "Could not locate a BEE2 compiler. Tried: {}.",
+        tried.join(", ")
+    ))
+}
+
+/// Look for the conventionally-placed `BEE2.py`: first beside this shim, then
+/// in the current working directory.
+fn find_script() -> Option<String> {
+    if let Some(dir) = env::current_exe().ok().and_then(|p| p.parent().map(Path::to_path_buf)) {
+        let beside = dir.join("BEE2.py");
+        if beside.exists() {
+            return Some(beside.to_string_lossy().into_owned());
+        }
+    }
+    let cwd = Path::new("BEE2.py");
+    cwd.exists().then(|| "BEE2.py".to_string())
+}
+
+/// Find the first of `names` present on `PATH`, returning its full path.
+fn find_on_path(names: &[&str]) -> Option<String> {
+    let path = env::var_os("PATH")?;
+    for dir in env::split_paths(&path) {
+        for name in names {
+            let candidate = dir.join(name);
+            if candidate.is_file() {
+                return Some(candidate.to_string_lossy().into_owned());
+            }
+            // Windows executables carry an extension.
+            let exe = dir.join(format!("{}.exe", name));
+            if exe.is_file() {
+                return Some(exe.to_string_lossy().into_owned());
+            }
+        }
+    }
+    None
+}
+
+/// Turn a resolved [`Profile`] back into the `[frozen]`/`[source]` pair used
+/// to store it.
+fn profile_to_set(profile: &Profile) -> ProfileSet {
+    match profile {
+        Profile::Frozen { exe } => ProfileSet {
+            frozen: Some(FrozenSection { exe: exe.clone() }),
+            source: None,
+        },
+        Profile::Source {
+            exe,
+            script,
+            args,
+            env,
+        } => ProfileSet {
+            frozen: None,
+            source: Some(SourceSection {
+                exe: exe.clone(),
+                script: script.clone(),
+                args: args.clone(),
+                env: env.clone(),
+            }),
+        },
+    }
+}
+
+/// Fold a newly detected profile for `comp_name` into `existing`. Only the
+/// section that was stale (the per-compiler override for `comp_name` if one
+/// existed, otherwise the global profile) is replaced; everything else
+/// `existing` held — other compilers' overrides, the `replace` rewrite rules —
+/// is carried over untouched.
+fn merge_detected_profile(comp_name: &str, existing: Option<&Config>, profile: &Profile) -> Config {
+    let mut config = existing.cloned().unwrap_or_default();
+    let set = profile_to_set(profile);
+    // Mirror Config::resolve's own pick logic: an override table that's present
+    // but empty (no [frozen]/[source] in it) isn't actually what was resolved,
+    // so it's the global profile that was stale, not the override.
+    let override_was_used = config.overrides.get(comp_name).and_then(ProfileSet::pick).is_some();
+    if override_was_used {
+        config.overrides.insert(comp_name.to_string(), set);
+    } else {
+        config.global = set;
+    }
+    config
+}
+
+/// Persist an autodetected profile back to `app_loc.paths` in the current TOML
+/// format so subsequent runs skip the probe.
+fn cache_profile(comp_name: &str, existing: Option<&Config>, profile: &Profile) -> Result<(), String> {
+    let config = merge_detected_profile(comp_name, existing, profile);
+    let text = toml::to_string(&config).map_err(|e| e.to_string())?;
+    fs::create_dir_all("bee2").map_err(|e| e.to_string())?;
+    fs::write("bee2/app_loc.paths", text).map_err(|e| e.to_string())
+}
+
 pub fn run_compiler(comp_name: &str) {
+    if let Err(err) = try_run_compiler(comp_name) {
+        eprintln!("BEE compiler hook: {}", err);
+        process::exit(1);
+    }
+}
+
+fn try_run_compiler(comp_name: &str) -> Result<(), String> {
     println!("BEE compiler hook for {} started.", comp_name);
 
-    // Grab the config left by the app.
-    let conf = parse_config(fs::read_to_string("bee2/app_loc.paths").expect("No BEE config file."));
-    println!("Config: {:?}", conf);
+    // Grab the config left by the app, falling back to autodetection when it is
+    // missing or points at a compiler that has since been moved away.
+    let (profile, replace) = resolve_profile(comp_name)?;
+    println!("Profile: {:?}", profile);
+
     let mut cmd;
-    match conf {
-        Config::Frozen(exe) => {
+    match &profile {
+        Profile::Frozen { exe } => {
             cmd = process::Command::new(exe);
         }
-        Config::PySource { exe, script } => {
+        Profile::Source {
+            exe,
+            script,
+            args,
+            env,
+        } => {
             cmd = process::Command::new(exe);
             cmd.arg(script);
+            cmd.args(args);
+            cmd.envs(env);
         }
     };
     // Add on the compiler to use.
@@ -42,14 +442,381 @@ pub fn run_compiler(comp_name: &str) {
     // Remove ourselves from the args list.
     cmd.args(env::args().skip(1));
     println!("Spawning compiler: {:?}", cmd);
-    let result = cmd
-        .spawn().expect("Could not start compiler.")
-        .wait().expect("Could not wait for compiler.");
-    process::exit(match result.code() {
+
+    // Snapshot the fully expanded argument vector for the run log.
+    let mut argv = vec![cmd.get_program().to_string_lossy().into_owned()];
+    argv.extend(cmd.get_args().map(|a| a.to_string_lossy().into_owned()));
+
+    // Put the child in its own process group so a forwarded Ctrl-C reaches it
+    // (and only it) rather than being delivered straight to the shim.
+    signals::new_process_group(&mut cmd);
+
+    // With no rewrite rules configured, inherit stdio directly so we never
+    // buffer a multi-gigabyte VRAD log. Otherwise capture both streams and run
+    // them through the rules before forwarding.
+    //
+    // Either way the child handle is registered with the signal handler while
+    // we wait, so an interrupt is forwarded instead of orphaning the compiler,
+    // and cleared again on the normal exit path to avoid a double-kill race.
+    let start = Instant::now();
+    let status = if replace.is_empty() {
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Could not start compiler: {}", e))?;
+        signals::install(&child);
+        let status = child
+            .wait()
+            .map_err(|e| format!("Could not wait for compiler: {}", e));
+        signals::clear();
+        status?
+    } else {
+        let rules = compile_rules(&replace)?;
+        cmd.stdout(process::Stdio::piped());
+        cmd.stderr(process::Stdio::piped());
+        let child = cmd
+            .spawn()
+            .map_err(|e| format!("Could not start compiler: {}", e))?;
+        signals::install(&child);
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Could not wait for compiler: {}", e));
+        signals::clear();
+        let output = output?;
+        print!("{}", rewrite_output(&output.stdout, &rules));
+        eprint!("{}", rewrite_output(&output.stderr, &rules));
+        output.status
+    };
+    let elapsed = start.elapsed();
+
+    // Logging must never be fatal to the compile itself.
+    if let Err(err) = log_compile(comp_name, &argv, status, elapsed) {
+        eprintln!("BEE compiler hook: could not write compile log: {}", err);
+    }
+
+    process::exit(exit_code(status));
+}
+
+/// Map a finished process status to the exit code the shim should return.
+fn exit_code(status: process::ExitStatus) -> i32 {
+    match status.code() {
         Some(code) => code,
         None => {
             eprintln!("Terminated by signal.");
             1
         }
-    });
+    }
+}
+
+/// A single line of `bee2/compile_log.jsonl`.
+#[derive(Serialize)]
+struct CompileRecord<'a> {
+    /// Seconds since the Unix epoch at which the run finished.
+    timestamp: u64,
+    comp_name: &'a str,
+    argv: &'a [String],
+    /// The process exit code, or `null` when killed by a signal.
+    exit_code: Option<i32>,
+    /// The terminating signal number on Unix, when applicable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signal: Option<i32>,
+    /// Wall-clock duration of the run, in milliseconds.
+    elapsed_ms: u128,
+}
+
+/// Signal that terminated the child, if any. Always `None` off Unix.
+#[cfg(unix)]
+fn termination_signal(status: process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn termination_signal(_status: process::ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Append a structured record of one compile to `bee2/compile_log.jsonl`,
+/// creating the `bee2` directory if it does not yet exist.
+fn log_compile(
+    comp_name: &str,
+    argv: &[String],
+    status: process::ExitStatus,
+    elapsed: std::time::Duration,
+) -> Result<(), String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+    let record = CompileRecord {
+        timestamp,
+        comp_name,
+        argv,
+        exit_code: status.code(),
+        signal: termination_signal(status),
+        elapsed_ms: elapsed.as_millis(),
+    };
+    let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+
+    fs::create_dir_all("bee2").map_err(|e| e.to_string())?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open("bee2/compile_log.jsonl")
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Cross-platform signal forwarding: register the running child so an interrupt
+/// delivered to the shim is relayed to the real compiler before we exit.
+#[cfg(unix)]
+mod signals {
+    use std::process::Child;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    /// PID of the child to forward to, or `0` when none is running.
+    static CHILD_PID: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn handler(sig: libc::c_int) {
+        let pid = CHILD_PID.load(Ordering::SeqCst);
+        if pid > 0 {
+            unsafe {
+                // Negative pid targets the whole process group.
+                libc::kill(-pid, sig);
+                // Give the compiler a moment to tear down cleanly.
+                libc::sleep(1);
+            }
+        }
+        // Restore the default action and re-raise so the shim itself dies.
+        unsafe {
+            libc::signal(sig, libc::SIG_DFL);
+            libc::raise(sig);
+        }
+    }
+
+    /// Run the child in its own process group so the forwarded signal only
+    /// reaches the compiler tree.
+    pub fn new_process_group(cmd: &mut std::process::Command) {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+
+    pub fn install(child: &Child) {
+        CHILD_PID.store(child.id() as i32, Ordering::SeqCst);
+        // Cast through a pointer first: casting a fn item directly to an
+        // integer type is a default-deny lint on current rustc.
+        unsafe {
+            libc::signal(libc::SIGINT, handler as *const () as libc::sighandler_t);
+            libc::signal(libc::SIGTERM, handler as *const () as libc::sighandler_t);
+        }
+    }
+
+    pub fn clear() {
+        CHILD_PID.store(0, Ordering::SeqCst);
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::signal(libc::SIGTERM, libc::SIG_DFL);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod signals {
+    use std::process::Child;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use winapi::shared::minwindef::{BOOL, DWORD, FALSE, TRUE};
+    use winapi::um::consoleapi::SetConsoleCtrlHandler;
+    use winapi::um::wincon::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+    use winapi::um::winbase::CREATE_NEW_PROCESS_GROUP;
+
+    /// Process-group id of the child, or `0` when none is running.
+    static CHILD_PID: AtomicU32 = AtomicU32::new(0);
+
+    unsafe extern "system" fn handler(_ctrl_type: DWORD) -> BOOL {
+        let pid = CHILD_PID.load(Ordering::SeqCst);
+        if pid != 0 {
+            // The child leads its own group; relay the break event to it.
+            GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid);
+            std::thread::sleep(Duration::from_secs(1));
+        }
+        // Let the default handler run so the shim also terminates.
+        FALSE
+    }
+
+    pub fn new_process_group(cmd: &mut std::process::Command) {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+    }
+
+    pub fn install(child: &Child) {
+        CHILD_PID.store(child.id(), Ordering::SeqCst);
+        unsafe {
+            SetConsoleCtrlHandler(Some(handler), TRUE);
+        }
+    }
+
+    pub fn clear() {
+        CHILD_PID.store(0, Ordering::SeqCst);
+        unsafe {
+            SetConsoleCtrlHandler(Some(handler), FALSE);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod signals {
+    use std::process::Child;
+
+    pub fn new_process_group(_cmd: &mut std::process::Command) {}
+    pub fn install(_child: &Child) {}
+    pub fn clear() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_legacy_frozen_is_a_single_line() {
+        let config = parse_legacy("C:\\BEE2\\compiler.exe");
+        assert!(config.global.source.is_none());
+        assert_eq!(config.global.frozen.unwrap().exe, "C:\\BEE2\\compiler.exe");
+    }
+
+    #[test]
+    fn parse_legacy_source_is_exe_then_script() {
+        let config = parse_legacy("/usr/bin/python3\n/opt/bee2/BEE2.py");
+        assert!(config.global.frozen.is_none());
+        let source = config.global.source.unwrap();
+        assert_eq!(source.exe, "/usr/bin/python3");
+        assert_eq!(source.script, "/opt/bee2/BEE2.py");
+    }
+
+    #[test]
+    fn parse_legacy_strips_trailing_crlf() {
+        let config = parse_legacy("C:\\BEE2\\compiler.exe\r\n");
+        assert_eq!(config.global.frozen.unwrap().exe, "C:\\BEE2\\compiler.exe");
+    }
+
+    #[test]
+    fn parse_config_reads_toml() {
+        let config = parse_config("[frozen]\nexe = \"compiler.exe\"\n").unwrap();
+        assert_eq!(config.global.frozen.unwrap().exe, "compiler.exe");
+    }
+
+    #[test]
+    fn parse_config_falls_back_to_legacy_when_path_contains_equals() {
+        // Not valid TOML, but also not more than the two lines the legacy
+        // format can ever have, so this must fall back rather than hard-error.
+        let config = parse_config("/usr/bin/python3\n/opt/a=b/BEE2.py").unwrap();
+        let source = config.global.source.unwrap();
+        assert_eq!(source.script, "/opt/a=b/BEE2.py");
+    }
+
+    #[test]
+    fn parse_config_rejects_genuinely_invalid_toml() {
+        let err = parse_config("[frozen\nexe = \"compiler.exe\"\nextra\nlines\nhere").unwrap_err();
+        assert!(err.contains("Invalid config"));
+    }
+
+    #[test]
+    fn rewrite_output_preserves_line_boundaries() {
+        let rules = compile_rules(&[ReplaceRule {
+            find: "C:\\game\\bee2".to_string(),
+            replace: "<GAME>".to_string(),
+            regex: false,
+        }])
+        .unwrap();
+        let raw = b"Loading C:\\game\\bee2\\maps\nDone\n";
+        assert_eq!(rewrite_output(raw, &rules), "Loading <GAME>\\maps\nDone\n");
+    }
+
+    #[test]
+    fn rewrite_output_keeps_a_trailing_line_without_newline() {
+        let rules = compile_rules(&[]).unwrap();
+        assert_eq!(rewrite_output(b"no newline here", &rules), "no newline here");
+    }
+
+    #[test]
+    fn rewrite_output_applies_regex_rules() {
+        let rules = compile_rules(&[ReplaceRule {
+            find: "\\\\".to_string(),
+            replace: "/".to_string(),
+            regex: true,
+        }])
+        .unwrap();
+        assert_eq!(rewrite_output(b"a\\b\\c\n", &rules), "a/b/c\n");
+    }
+
+    #[test]
+    fn merge_detected_profile_preserves_replace_rules_and_other_overrides() {
+        let existing = parse_config(
+            "[frozen]\nexe = \"old_compiler.exe\"\n\
+             [[replace]]\nfind = \"C:\\\\game\"\nreplace = \"<GAME>\"\n\
+             [vrad.frozen]\nexe = \"vrad_compiler.exe\"\n",
+        )
+        .unwrap();
+
+        // `vbsp`'s global frozen exe went stale; only it should be updated.
+        let detected = Profile::Frozen {
+            exe: "new_compiler.exe".to_string(),
+        };
+        let merged = merge_detected_profile("vbsp", Some(&existing), &detected);
+
+        assert_eq!(merged.global.frozen.unwrap().exe, "new_compiler.exe");
+        assert_eq!(merged.replace.len(), 1);
+        assert_eq!(merged.replace[0].find, "C:\\game");
+        assert_eq!(merged.overrides["vrad"].frozen.as_ref().unwrap().exe, "vrad_compiler.exe");
+    }
+
+    #[test]
+    fn merge_detected_profile_updates_stale_override_in_place() {
+        let existing =
+            parse_config("[vbsp.frozen]\nexe = \"old_vbsp.exe\"\n[vrad.frozen]\nexe = \"vrad.exe\"\n").unwrap();
+        let detected = Profile::Frozen {
+            exe: "new_vbsp.exe".to_string(),
+        };
+        let merged = merge_detected_profile("vbsp", Some(&existing), &detected);
+
+        assert_eq!(merged.overrides["vbsp"].frozen.as_ref().unwrap().exe, "new_vbsp.exe");
+        assert_eq!(merged.overrides["vrad"].frozen.as_ref().unwrap().exe, "vrad.exe");
+        assert!(merged.global.frozen.is_none());
+    }
+
+    #[test]
+    fn merge_detected_profile_fixes_global_when_override_table_is_empty() {
+        // An empty [vbsp] table is legal TOML but ProfileSet::pick() returns
+        // None for it, so Config::resolve falls back to the (stale) global
+        // profile; the merge must patch global, not the empty override.
+        let existing = parse_config("[frozen]\nexe = \"old_global.exe\"\n[vbsp]\ncomment = \"todo\"\n").unwrap();
+        let detected = Profile::Frozen {
+            exe: "new_global.exe".to_string(),
+        };
+        let merged = merge_detected_profile("vbsp", Some(&existing), &detected);
+
+        assert_eq!(merged.global.frozen.unwrap().exe, "new_global.exe");
+        assert!(merged.overrides["vbsp"].pick().is_none());
+    }
+
+    #[test]
+    fn resolve_errs_when_no_profile_is_configured_anywhere() {
+        // resolve_profile relies on this: a config with only replace rules and
+        // no [frozen]/[source] must come back as an error here so it falls
+        // through to autodetect rather than being treated as resolved.
+        let config = parse_config("[[replace]]\nfind = \"x\"\nreplace = \"y\"\n").unwrap();
+        assert!(config.resolve("vbsp").is_err());
+    }
+
+    #[test]
+    fn compile_rules_rejects_invalid_regex() {
+        let err = compile_rules(&[ReplaceRule {
+            find: "(unclosed".to_string(),
+            replace: String::new(),
+            regex: true,
+        }])
+        .unwrap_err();
+        assert!(err.contains("Invalid replace regex"));
+    }
 }